@@ -0,0 +1,122 @@
+//! A status-code keyed error handler middleware, analogous to the
+//! `ErrorHandlers` middleware actix itself ships in newer releases.
+//!
+//! Instead of every `Fail` type re-implementing `error_response` by hand to
+//! get a consistent body/`Content-Type`, handlers are registered once on the
+//! `App` and run after the inner service, rewriting the response for any
+//! status code that has one.
+//!
+//! Like the real middleware, this is generic over the response body type
+//! `B` rather than fixed to `Body`: whatever sits directly under it in the
+//! `.wrap()` stack (here, the app's own services) determines `B`, so this
+//! middleware has to be placed *inside* `Logger` — `Logger` changes the
+//! body type to `StreamLog<B>` on its way out.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use actix_web::{Error, Result};
+use futures::future::{ok, FutureExt, LocalBoxFuture, Ready};
+
+/// What a registered handler hands back: either the rewritten response is
+/// ready immediately, or it needs to be computed asynchronously.
+pub enum ErrorHandlerResponse<B> {
+    /// The response, already rewritten.
+    Response(ServiceResponse<B>),
+    /// A future resolving to the rewritten response.
+    Future(LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>),
+}
+
+type Handler<B> = dyn Fn(ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>>;
+
+/// Middleware registering one handler per `StatusCode`.
+pub struct ErrorHandlers<B> {
+    handlers: Rc<HashMap<StatusCode, Box<Handler<B>>>>,
+}
+
+impl<B> Default for ErrorHandlers<B> {
+    fn default() -> Self {
+        ErrorHandlers {
+            handlers: Rc::new(HashMap::new()),
+        }
+    }
+}
+
+impl<B> ErrorHandlers<B> {
+    pub fn new() -> Self {
+        ErrorHandlers::default()
+    }
+
+    /// Register `handler` to run whenever a response carries `status`.
+    pub fn handler<F>(mut self, status: StatusCode, handler: F) -> Self
+    where
+        F: Fn(ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> + 'static,
+    {
+        Rc::get_mut(&mut self.handlers)
+            .expect("ErrorHandlers must be configured before the app starts")
+            .insert(status, Box::new(handler));
+        self
+    }
+}
+
+impl<S, B> Transform<S> for ErrorHandlers<B>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ErrorHandlersMiddleware<S, B>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ErrorHandlersMiddleware {
+            service,
+            handlers: self.handlers.clone(),
+        })
+    }
+}
+
+pub struct ErrorHandlersMiddleware<S, B> {
+    service: S,
+    handlers: Rc<HashMap<StatusCode, Box<Handler<B>>>>,
+}
+
+impl<S, B> Service for ErrorHandlersMiddleware<S, B>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let handlers = self.handlers.clone();
+        let fut = self.service.call(req);
+
+        async move {
+            let res = fut.await?;
+            match handlers.get(&res.status()) {
+                Some(handler) => match handler(res)? {
+                    ErrorHandlerResponse::Response(res) => Ok(res),
+                    ErrorHandlerResponse::Future(fut) => fut.await,
+                },
+                None => Ok(res),
+            }
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,221 @@
+//! A single error type for the app, replacing the overlapping `MyError`,
+//! `MyErrors` and `UserErrors` types that each re-implemented
+//! `error_response`/`status_code` by hand.
+//!
+//! `ResponseError::error_response` has no access to the request (so it can't
+//! look at `Accept` itself), so it renders HTML and tags the response with
+//! the structured fields as headers; the `ErrorHandlers` middleware from
+//! `error_handlers` (which does see the request) turns that into JSON when
+//! the client asks for it via `negotiate_json`.
+
+use std::fmt;
+
+use actix_web::body::Body;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::{header, StatusCode};
+use actix_web::{error, HttpResponse, Result};
+use failure::Fail;
+use futures::future::FutureExt;
+use log::debug;
+use serde::Serialize;
+
+use crate::error_handlers::ErrorHandlerResponse;
+
+const CODE_HEADER: &str = "x-app-error-code";
+const MESSAGE_HEADER: &str = "x-app-error-message";
+const FIELD_HEADER: &str = "x-app-error-field";
+
+/// A machine-readable error code, an HTTP status, an optional field name
+/// for validation failures, and the underlying cause (if any).
+#[derive(Debug)]
+pub struct AppError {
+    code: &'static str,
+    status: StatusCode,
+    message: String,
+    field: Option<&'static str>,
+    cause: Option<failure::Error>,
+}
+
+impl AppError {
+    pub fn internal() -> Self {
+        AppError {
+            code: "internal_error",
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "An internal error occurred. Please try again later.".to_string(),
+            field: None,
+            cause: None,
+        }
+    }
+
+    pub fn bad_request() -> Self {
+        AppError {
+            code: "bad_request",
+            status: StatusCode::BAD_REQUEST,
+            message: "bad request".to_string(),
+            field: None,
+            cause: None,
+        }
+    }
+
+    pub fn timeout() -> Self {
+        AppError {
+            code: "timeout",
+            status: StatusCode::GATEWAY_TIMEOUT,
+            message: "timeout".to_string(),
+            field: None,
+            cause: None,
+        }
+    }
+
+    pub fn validation(field: &'static str) -> Self {
+        AppError {
+            code: "validation_error",
+            status: StatusCode::BAD_REQUEST,
+            message: format!("Validation error on field: {}", field),
+            field: Some(field),
+            cause: None,
+        }
+    }
+
+    /// Attach the lower-level error that caused this one.
+    pub fn caused_by<E: Into<failure::Error>>(mut self, cause: E) -> Self {
+        self.cause = Some(cause.into());
+        self
+    }
+}
+
+/// Tags a lower-level error with the field that produced it, so `?` can
+/// turn it into a validation `AppError` via `From` below without a blanket
+/// `impl From<ParseIntError>` that would hard-code the field name for
+/// every call site in the app.
+pub struct FieldError<E> {
+    field: &'static str,
+    source: E,
+}
+
+/// Adds `.field(name)` to any `Result`, tagging its error for the `From<
+/// FieldError<E>> for AppError` conversion so a handler can write
+/// `"...".parse::<u32>().field("age")?` instead of a manual `map_err`.
+pub trait ResultFieldExt<T, E> {
+    fn field(self, field: &'static str) -> std::result::Result<T, FieldError<E>>;
+}
+
+impl<T, E> ResultFieldExt<T, E> for std::result::Result<T, E> {
+    fn field(self, field: &'static str) -> std::result::Result<T, FieldError<E>> {
+        self.map_err(|source| FieldError { field, source })
+    }
+}
+
+impl<E: Into<failure::Error>> From<FieldError<E>> for AppError {
+    fn from(err: FieldError<E>) -> Self {
+        AppError::validation(err.field).caused_by(err.source)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Fail for AppError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.cause.as_ref().map(|e| e.as_fail())
+    }
+}
+
+impl error::ResponseError for AppError {
+    fn error_response(&self) -> HttpResponse {
+        debug!("{}", self);
+        if let Some(cause) = self.cause() {
+            debug!("caused by: {}", cause);
+        }
+
+        let mut builder = HttpResponse::build(self.status);
+        builder
+            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .set_header(CODE_HEADER, self.code)
+            .set_header(MESSAGE_HEADER, self.message.clone());
+        if let Some(field) = self.field {
+            builder.set_header(FIELD_HEADER, field);
+        }
+        builder.body(self.message.clone())
+    }
+
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'a str>,
+}
+
+/// Status-code handler for `error_handlers::ErrorHandlers`: rewrite an
+/// `AppError` response as JSON when the request's `Accept` header asks for
+/// it, reading back the structured fields `error_response` stashed as
+/// headers. Responses from non-`AppError` sources fall back to a generic
+/// body built from the status code alone.
+pub fn negotiate_json(mut res: ServiceResponse<Body>) -> Result<ErrorHandlerResponse<Body>> {
+    let wants_json = res
+        .request()
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|accept| accept.contains("application/json"))
+        .unwrap_or(false);
+
+    let status = res.status();
+    let headers = res.headers();
+    let code = headers
+        .get(CODE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("error")
+        .to_string();
+    let message = headers
+        .get(MESSAGE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("error"))
+        .to_string();
+    let field = headers
+        .get(FIELD_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    // These are only a hand-off between `error_response` and this middleware
+    // and must never reach the client, whichever branch it takes below.
+    let headers = res.headers_mut();
+    headers.remove(CODE_HEADER);
+    headers.remove(MESSAGE_HEADER);
+    headers.remove(FIELD_HEADER);
+
+    if !wants_json {
+        return Ok(ErrorHandlerResponse::Response(res));
+    }
+
+    // Rendering the JSON body is cheap and synchronous today, but this is
+    // the hook a future `AppError` variant backed by, say, an async lookup
+    // for a localized message would use — so it's built inside the
+    // `Future` variant rather than the immediate `Response` one.
+    Ok(ErrorHandlerResponse::Future(
+        async move {
+            let body = serde_json::to_string(&ErrorBody {
+                code: &code,
+                message: &message,
+                field: field.as_deref(),
+            })
+            .unwrap_or_else(|_| "{}".to_string());
+
+            let response = HttpResponse::build(status)
+                .content_type("application/json; charset=utf-8")
+                .body(body);
+
+            Ok(res.into_response(response))
+        }
+        .boxed_local(),
+    ))
+}
@@ -0,0 +1,218 @@
+//! Serves a single file from disk, honoring `Range` requests for partial
+//! content and `If-None-Match`/`If-Modified-Since` for conditional requests,
+//! the same way a real static file server would.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{error::BlockingError, web, Error, HttpRequest, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, Stream};
+use httpdate::HttpDate;
+use log::debug;
+
+/// A file opened from disk, ready to be turned into a streaming response.
+pub struct NamedFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    modified: HttpDate,
+    etag: HeaderValue,
+    content_type: mime::Mime,
+}
+
+impl NamedFile {
+    /// Open `path` relative to `root`, reading just enough metadata to
+    /// answer conditional and range requests without holding the whole
+    /// file in memory.
+    ///
+    /// `path` is resolved against `root` and canonicalized; if the result
+    /// doesn't stay under `root` (e.g. `path` contains `..` or an absolute
+    /// path escaping it), this returns `NotFound` instead of touching
+    /// anything outside the configured root, mirroring the confinement
+    /// `actix-files::Files` does for its serving root.
+    pub fn open(root: &Path, path: &str) -> io::Result<NamedFile> {
+        let root = root.canonicalize()?;
+        let path = root.join(path).canonicalize()?;
+        if !path.starts_with(&root) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "requested path escapes the serving root",
+            ));
+        }
+
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        let modified: HttpDate = metadata.modified()?.into();
+        let etag = HeaderValue::from_str(&format!(
+            "\"{:x}-{:x}\"",
+            metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            metadata.len()
+        ))
+        .expect("etag is a valid header value");
+        let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+
+        Ok(NamedFile {
+            path,
+            file,
+            len: metadata.len(),
+            modified,
+            etag,
+            content_type,
+        })
+    }
+
+    /// Build the response for `req`, taking `Range`/`If-None-Match`/
+    /// `If-Modified-Since` into account. Mirrors how the custom responders
+    /// already take `&HttpRequest` to look at request headers.
+    pub fn into_response(mut self, req: &HttpRequest) -> Result<HttpResponse, Error> {
+        debug!("serving file {:?}", self.path);
+
+        if self.not_modified(req) {
+            return Ok(HttpResponse::NotModified()
+                .set_header(header::ETAG, self.etag.clone())
+                .set_header(header::LAST_MODIFIED, self.modified.to_string())
+                .finish());
+        }
+
+        match self.requested_range(req)? {
+            Some((start, end)) => {
+                let chunk_len = end - start + 1;
+                self.file.seek(SeekFrom::Start(start))?;
+
+                Ok(HttpResponse::build(StatusCode::PARTIAL_CONTENT)
+                    .content_type(self.content_type.to_string())
+                    .set_header(header::ETAG, self.etag)
+                    .set_header(header::LAST_MODIFIED, self.modified.to_string())
+                    .set_header(header::ACCEPT_RANGES, "bytes")
+                    .set_header(
+                        header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{}", start, end, self.len),
+                    )
+                    .set_header(header::CONTENT_LENGTH, chunk_len.to_string())
+                    .streaming(chunked_read(self.file, chunk_len)))
+            }
+            None => Ok(HttpResponse::Ok()
+                .content_type(self.content_type.to_string())
+                .set_header(header::ETAG, self.etag)
+                .set_header(header::LAST_MODIFIED, self.modified.to_string())
+                .set_header(header::ACCEPT_RANGES, "bytes")
+                .set_header(header::CONTENT_LENGTH, self.len.to_string())
+                .streaming(chunked_read(self.file, self.len))),
+        }
+    }
+
+    fn not_modified(&self, req: &HttpRequest) -> bool {
+        if let Some(if_none_match) = req.headers().get(header::IF_NONE_MATCH) {
+            return if_none_match == self.etag;
+        }
+
+        if let Some(if_modified_since) = req.headers().get(header::IF_MODIFIED_SINCE) {
+            if let Ok(since) = if_modified_since.to_str() {
+                if let Ok(since) = since.parse::<HttpDate>() {
+                    return self.modified <= since;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Parse a single-range `Range: bytes=start-end` header into an
+    /// inclusive `(start, end)` byte range, clamped to the file's length.
+    fn requested_range(&self, req: &HttpRequest) -> Result<Option<(u64, u64)>, Error> {
+        let range = match req.headers().get(header::RANGE) {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+
+        let range = range
+            .to_str()
+            .ok()
+            .and_then(|r| r.strip_prefix("bytes="))
+            .ok_or_else(|| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?;
+
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?;
+
+        let last = self.len.saturating_sub(1);
+        let (start, end) = match (start, end) {
+            ("", suffix) => {
+                let suffix: u64 = suffix
+                    .parse()
+                    .map_err(|_| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?;
+                (self.len.saturating_sub(suffix), last)
+            }
+            (start, "") => (
+                start
+                    .parse()
+                    .map_err(|_| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?,
+                last,
+            ),
+            (start, end) => (
+                start
+                    .parse()
+                    .map_err(|_| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?,
+                end.parse()
+                    .map_err(|_| actix_web::error::ErrorRangeNotSatisfiable("malformed Range header"))?,
+            ),
+        };
+
+        let end = end.min(last);
+        if start > end {
+            return Err(actix_web::error::ErrorRangeNotSatisfiable(format!(
+                "range {}-{} out of bounds for a {}-byte file",
+                start, end, self.len
+            )));
+        }
+
+        Ok(Some((start, end)))
+    }
+}
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Turn an already-seeked `File` into a `Stream` of `Bytes` chunks, reading
+/// at most `remaining` bytes total, the streaming counterpart to the
+/// in-memory body the `/stream` handler returns.
+///
+/// Each chunk is read via `web::block` rather than calling `Read::read`
+/// straight from this `async fn` — a blocking disk read here would stall
+/// the whole worker thread for every other request it's juggling.
+fn chunked_read(file: File, remaining: u64) -> impl Stream<Item = Result<Bytes, Error>> {
+    stream::unfold((Some(file), remaining), |(file, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let file = file?;
+
+        let to_read = remaining.min(CHUNK_SIZE) as usize;
+        let read = web::block(move || -> io::Result<(File, BytesMut)> {
+            let mut file = file;
+            let mut buf = BytesMut::from(vec![0u8; to_read].as_slice());
+            let n = file.read(&mut buf)?;
+            buf.truncate(n);
+            Ok((file, buf))
+        })
+        .await;
+
+        match read {
+            Ok((_, buf)) if buf.is_empty() => None,
+            Ok((file, buf)) => {
+                let n = buf.len() as u64;
+                Some((Ok(buf.freeze()), (Some(file), remaining - n)))
+            }
+            Err(BlockingError::Error(e)) => Some((Err(Error::from(e)), (None, 0))),
+            Err(BlockingError::Canceled) => None,
+        }
+    })
+}
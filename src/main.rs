@@ -1,20 +1,26 @@
-use actix_http::ResponseBuilder;
-use actix_web::http::{header, StatusCode};
+use actix_web::http::StatusCode;
 use actix_web::{
     error, get, middleware::Logger, post, web, App, Error, HttpRequest, HttpResponse, HttpServer,
     Responder, Result,
 };
-use failure::Fail;
-use futures::future::{ok, ready, Ready};
-use futures::stream::once;
+use futures::future::{ready, Ready};
+use futures::stream::iter;
 
 use serde::{Deserialize, Serialize};
 
-use log::debug;
-
 use bytes::Bytes;
+use std::path::Path;
 use std::sync::Mutex;
 
+mod error_handlers;
+mod errors;
+mod files;
+mod streaming;
+
+use error_handlers::ErrorHandlers;
+use errors::{AppError, ResultFieldExt};
+use files::NamedFile;
+
 // 1. Use request handlers to extract parameters from a request(trait:FromRequest) and return a response(trait:Responder).
 // 2. By default actix-web provides Responder implementations for some standard types, such as &'static str, String, etc.
 #[get("/")]
@@ -90,96 +96,76 @@ impl Responder for MyObj {
 #[get("/custom")]
 async fn custom() -> impl Responder {
     MyObj { name: "user" }
+        .with_status(StatusCode::CREATED)
+        .with_header("X-App", "hello-actix")
 }
 
 // 7. Return stream response
 #[get("/stream")]
 async fn stream() -> HttpResponse {
-    let body = once(ok::<_, Error>(Bytes::from_static(b"stream")));
+    // A real streaming source can fail partway through; wrap it with
+    // `streaming::resilient` so an `Err` becomes a trailing error chunk
+    // instead of truncating the body.
+    let body = streaming::resilient(iter(vec![
+        Ok(Bytes::from_static(b"stream")),
+        Err(AppError::internal()),
+    ]));
 
     HttpResponse::Ok()
         .content_type("application/json")
         .streaming(body)
 }
 
-// 8. Customize error responses to return 500 server internal error.
-#[derive(Fail, Debug)]
-#[fail(display = "my error")] // 500 status code with title "my error"
-struct MyError {
-    name: &'static str,
+// 7b. Serve a file from disk, honoring Range and conditional request headers.
+const FILES_ROOT: &str = "./files";
+
+#[get("/files/{path:.*}")]
+async fn serve_file(req: HttpRequest, path: web::Path<(String,)>) -> Result<HttpResponse> {
+    let file = NamedFile::open(Path::new(FILES_ROOT), &path.0).map_err(error::ErrorNotFound)?;
+    file.into_response(&req)
 }
-impl error::ResponseError for MyError {}
 
+// 8. Customize error responses to return 500 server internal error.
 #[get("/fail")]
-async fn fail() -> Result<&'static str, MyError> {
-    let err = MyError { name: "test fail" };
-    debug!("{}", err);
-    Err(err)
+async fn fail() -> Result<&'static str, AppError> {
+    Err(AppError::internal())
 }
 
 // 9. Build server error module
-#[derive(Fail, Debug)]
-enum MyErrors {
-    #[fail(display = "internal error")]
-    InternalError,
-    #[fail(display = "bad request")]
-    BadClientData,
-    #[fail(display = "timeout")]
-    Timeout,
-}
-impl error::ResponseError for MyErrors {
-    fn error_response(&self) -> HttpResponse {
-        ResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
-    }
-
-    fn status_code(&self) -> StatusCode {
-        match *self {
-            MyErrors::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
-            MyErrors::BadClientData => StatusCode::BAD_REQUEST,
-            MyErrors::Timeout => StatusCode::GATEWAY_TIMEOUT,
-        }
-    }
-}
-
 #[get("/bad-data")]
-async fn bad_data() -> Result<&'static str, MyErrors> {
-    Err(MyErrors::BadClientData)
+async fn bad_data() -> Result<&'static str, AppError> {
+    Err(AppError::bad_request())
 }
 
 // 10. Build user error module
-#[derive(Fail, Debug)]
-enum UserErrors {
-    #[fail(display = "Validation error on field: {}", field)]
-    ValidationError { field: &'static str },
-    #[fail(display = "An internal error occurred. Please try again later.")]
-    InternalError,
-}
-impl error::ResponseError for UserErrors {
-    fn error_response(&self) -> HttpResponse {
-        ResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
-    }
-    fn status_code(&self) -> StatusCode {
-        match *self {
-            UserErrors::ValidationError { .. } => StatusCode::BAD_REQUEST,
-            UserErrors::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
-        }
-    }
-}
-
 #[get("/user-error")]
-async fn user_error() -> Result<&'static str, UserErrors> {
-    validate_user_input_error().map_err(|_e| UserErrors::ValidationError { field: "name" })?;
+async fn user_error() -> Result<&'static str, AppError> {
+    validate_user_input()?;
     Ok("success!")
 }
 
-fn validate_user_input_error() -> Result<(), MyError> {
-    Err(MyError {
-        name: "input error",
-    })
+// 11. Exercise the gateway-timeout handler registered in `error_handlers()`.
+#[get("/timeout")]
+async fn timeout() -> Result<&'static str, AppError> {
+    Err(AppError::timeout())
+}
+
+fn validate_user_input() -> Result<(), AppError> {
+    // A real lower-level failure (not a hand-rolled one), propagated with
+    // `?` via `AppError`'s `From<FieldError<_>>` impl, cause and all —
+    // `.field("name")` tags which field it came from instead of a blanket
+    // `From<ParseIntError>` hard-coding that for every call site.
+    "not-a-number".parse::<u32>().field("name")?;
+    Ok(())
+}
+
+// 12. Centralize error response rendering behind one middleware instead of
+// every `Fail` type re-implementing `error_response`.
+fn error_handlers() -> ErrorHandlers<actix_web::body::Body> {
+    ErrorHandlers::new()
+        .handler(StatusCode::BAD_REQUEST, errors::negotiate_json)
+        .handler(StatusCode::INTERNAL_SERVER_ERROR, errors::negotiate_json)
+        .handler(StatusCode::GATEWAY_TIMEOUT, errors::negotiate_json)
 }
 
 // start point
@@ -197,13 +183,20 @@ async fn main() -> std::io::Result<()> {
     // Use App factory to register routes, middlewares and to store state. The shared data has to be thread-safe.
     HttpServer::new(move || {
         App::new()
+            // `error_handlers()` must wrap the raw app services (so its `B`
+            // is plain `Body`), with `Logger` outside it — `Logger` changes
+            // the body type to `StreamLog<B>` on the way out, and the last
+            // `.wrap()` call is the outermost layer.
+            .wrap(error_handlers())
             .wrap(Logger::default())
             .service(index)
             .service(custom)
             .service(stream)
+            .service(serve_file)
             .service(fail)
             .service(bad_data)
             .service(user_error)
+            .service(timeout)
             .service(web::scope("/user").configure(user_config)) // Include the configuration.
             .service(web::scope("/app1").app_data(state.clone()).service(app1)) // Clone the state for each thread in the scope.
     })
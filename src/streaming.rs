@@ -0,0 +1,38 @@
+//! Wraps a fallible byte stream so an error partway through doesn't
+//! silently truncate the response body.
+//!
+//! Headers are already sent by the time a streaming body is being
+//! polled, so there's no way to turn a mid-stream failure into a
+//! different status code. Instead, once a source stream yields an
+//! `Err(AppError)`, [`resilient`] logs it and emits one more chunk — a
+//! JSON `{"error": ...}` trailer — then ends the body.
+
+use actix_web::web::Bytes;
+use actix_web::Error;
+use futures::stream::{self, Stream, StreamExt};
+use log::error;
+
+use crate::errors::AppError;
+
+/// Turn a `Stream<Item = Result<Bytes, AppError>>` into a body stream that
+/// never errors: failures become a trailing JSON chunk instead.
+pub fn resilient<S>(source: S) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, AppError>> + Unpin,
+{
+    stream::unfold((source, false), |(mut source, done)| async move {
+        if done {
+            return None;
+        }
+
+        match source.next().await {
+            Some(Ok(bytes)) => Some((Ok(bytes), (source, false))),
+            Some(Err(err)) => {
+                error!("stream failed mid-response: {}", err);
+                let trailer = serde_json::json!({ "error": err.to_string() }).to_string();
+                Some((Ok(Bytes::from(trailer)), (source, true)))
+            }
+            None => None,
+        }
+    })
+}